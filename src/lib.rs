@@ -1,49 +1,351 @@
 const MEMORY_BUFFER_SIZE: usize = 128;
-const SALUT_OFFSET: usize = 0;
-const NAME_OFFSET: usize = 16;
-const MSG_OFFSET: usize = 32;
+
+const NUL: u8 = 0;
 
 static mut BUFFER: [u8; MEMORY_BUFFER_SIZE] = [0; MEMORY_BUFFER_SIZE];
+static mut FREE_CURSOR: usize = 0;
+static mut LIVE_REGIONS: Vec<(usize, usize)> = Vec::new();
+static mut LAST_ERROR_OFFSET: i32 = -1;
+static mut LAST_MSG_HANDLE: i32 = -1;
+static mut LAST_CSTR_MSG_HANDLE: i32 = -1;
+
+/// A read-only view over a `(offset, len)` region of `BUFFER`, validated against `MEMORY_BUFFER_SIZE` at
+/// construction
+struct ReadView<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ReadView<'a> {
+    fn new(offset: usize, len: usize) -> Option<Self> {
+        if offset + len > MEMORY_BUFFER_SIZE {
+            return None;
+        }
+
+        Some(ReadView {
+            bytes: unsafe { &BUFFER[offset..(offset + len)] },
+        })
+    }
+
+    fn as_str(&self) -> Result<&'a str, std::str::Utf8Error> {
+        std::str::from_utf8(self.bytes)
+    }
+}
+
+/// The write-side counterpart of `ReadView`
+struct WriteView<'a> {
+    bytes: &'a mut [u8],
+}
+
+impl<'a> WriteView<'a> {
+    fn new(offset: usize, len: usize) -> Option<Self> {
+        if offset + len > MEMORY_BUFFER_SIZE {
+            return None;
+        }
 
+        Some(WriteView {
+            bytes: unsafe { &mut BUFFER[offset..(offset + len)] },
+        })
+    }
+
+    fn copy_from_slice(&mut self, src: &[u8]) {
+        self.bytes.copy_from_slice(src);
+    }
+}
+
+/// Everything that can go wrong reading a region: it falls outside the buffer, or its bytes aren't valid UTF-8
+enum BufferError {
+    OutOfBounds,
+    InvalidUtf8(std::str::Utf8Error),
+}
+
+/// Bump-allocate `len` bytes from the shared buffer and return the offset of the new region, or `-1` if
+/// insufficient space remains; use `reset()` to reclaim the buffer once every region is done with
 #[no_mangle]
-pub extern "C" fn get_salutation_ptr() -> *const u8 {
-    get_ptr(SALUT_OFFSET)
+pub extern "C" fn alloc(len: i32) -> i32 {
+    if len < 0 {
+        return -1;
+    }
+
+    unsafe {
+        let len = len as usize;
+        if FREE_CURSOR + len > MEMORY_BUFFER_SIZE {
+            return -1;
+        }
+
+        let offset = FREE_CURSOR;
+        LIVE_REGIONS.push((offset, offset + len));
+        FREE_CURSOR += len;
+        offset as i32
+    }
 }
 
+/// Pointer to the region previously reserved with `alloc`, or null if `handle` isn't a live region
 #[no_mangle]
-pub extern "C" fn get_name_ptr() -> *const u8 {
-    get_ptr(NAME_OFFSET)
+pub extern "C" fn region_ptr(handle: i32) -> *const u8 {
+    match region_end(handle as usize) {
+        Some(_) => get_ptr(handle as usize),
+        None => std::ptr::null(),
+    }
 }
 
+/// Rewind the allocator, discarding every live region so the buffer can be reused from offset `0`
 #[no_mangle]
-pub extern "C" fn get_msg_ptr() -> *const u8 {
-    get_ptr(MSG_OFFSET)
+pub extern "C" fn reset() {
+    unsafe {
+        FREE_CURSOR = 0;
+        LIVE_REGIONS.clear();
+        LAST_ERROR_OFFSET = -1;
+        LAST_MSG_HANDLE = -1;
+        LAST_CSTR_MSG_HANDLE = -1;
+    }
 }
 
+/// Build the greeting "<salutation>, <name>!" from the two given regions, allocate a fresh region to hold it
+/// and return its length, or a negative status code on failure (see `last_error_offset()`/`last_msg_handle()`)
 #[no_mangle]
-pub extern "C" fn set_name(sal_len: i32, name_len: i32) -> i32 {
-    let sal: &str = str_from_buffer(SALUT_OFFSET, sal_len as usize);
-    let name: &str = str_from_buffer(NAME_OFFSET, name_len as usize);
+pub extern "C" fn set_name(sal_handle: i32, sal_len: i32, name_handle: i32, name_len: i32) -> i32 {
+    unsafe {
+        LAST_ERROR_OFFSET = -1;
+    }
+
+    let sal_len = match region_len(sal_handle as usize, sal_len) {
+        Some(len) => len,
+        None => return -1,
+    };
+    let name_len = match region_len(name_handle as usize, name_len) {
+        Some(len) => len,
+        None => return -1,
+    };
+
+    let sal: &str = match str_from_buffer(sal_handle as usize, sal_len) {
+        Ok(s) => s,
+        Err(e) => return fail(e),
+    };
+    let name: &str = match str_from_buffer(name_handle as usize, name_len) {
+        Ok(s) => s,
+        Err(e) => return fail(e),
+    };
 
     let greeting: String = format!("{}, {}!", sal, name);
 
+    let msg_handle = alloc(greeting.len() as i32);
+    if msg_handle < 0 || copy_bytes(msg_handle as usize, greeting.as_bytes()).is_err() {
+        return -1;
+    }
+
     unsafe {
-        greeting
-            .as_bytes()
-            .iter()
-            .enumerate()
-            .for_each(|(idx, byte): (usize, &u8)| {
-                BUFFER[MSG_OFFSET + idx] = *byte;
-            });
+        LAST_MSG_HANDLE = msg_handle;
+    }
+
+    greeting.len() as i32
+}
+
+/// NUL-terminated variant of `set_name` for callers that write C-style strings into the given regions instead
+/// of passing explicit lengths; returns a negative status code if either region has no terminator within its
+/// bounds (`-1000`/`-1001`) or fails for one of `set_name`'s reasons
+#[no_mangle]
+pub extern "C" fn set_name_cstr(sal_handle: i32, name_handle: i32) -> i32 {
+    unsafe {
+        LAST_ERROR_OFFSET = -1;
+    }
+
+    let sal_end = match region_end(sal_handle as usize) {
+        Some(end) => end,
+        None => return -1,
+    };
+    let name_end = match region_end(name_handle as usize) {
+        Some(end) => end,
+        None => return -1,
+    };
+
+    let sal_len = match cstr_len(sal_handle as usize, sal_end) {
+        Some(len) => len,
+        None => return -1000,
+    };
+    let name_len = match cstr_len(name_handle as usize, name_end) {
+        Some(len) => len,
+        None => return -1001,
+    };
+
+    let sal: &str = match str_from_buffer(sal_handle as usize, sal_len) {
+        Ok(s) => s,
+        Err(e) => return fail(e),
+    };
+    let name: &str = match str_from_buffer(name_handle as usize, name_len) {
+        Ok(s) => s,
+        Err(e) => return fail(e),
+    };
+
+    let greeting: String = format!("{}, {}!", sal, name);
+
+    let msg_handle = alloc((greeting.len() + 1) as i32);
+    if msg_handle < 0
+        || copy_bytes(msg_handle as usize, greeting.as_bytes()).is_err()
+        || copy_bytes(msg_handle as usize + greeting.len(), &[NUL]).is_err()
+    {
+        return -1;
+    }
+
+    unsafe {
+        LAST_MSG_HANDLE = msg_handle;
+        LAST_CSTR_MSG_HANDLE = msg_handle;
     }
 
     greeting.len() as i32
 }
 
+/// Length of the message most recently written by `set_name_cstr`, found by scanning for its NUL terminator
+///
+/// Only `set_name_cstr` writes a terminator, so a plain `set_name` call in between doesn't change what this
+/// returns
+#[no_mangle]
+pub extern "C" fn get_msg_len() -> i32 {
+    unsafe {
+        if LAST_CSTR_MSG_HANDLE < 0 {
+            return -1;
+        }
+
+        match cstr_len(LAST_CSTR_MSG_HANDLE as usize, MEMORY_BUFFER_SIZE) {
+            Some(len) => len as i32,
+            None => -1,
+        }
+    }
+}
+
+/// Handle of the region most recently written to by `set_name`/`set_name_cstr`, or `-1` if neither has
+/// succeeded yet
+#[no_mangle]
+pub extern "C" fn last_msg_handle() -> i32 {
+    unsafe { LAST_MSG_HANDLE }
+}
+
+/// Byte offset at which the most recent UTF-8 validation failure occurred, or `-1` if the last call succeeded
+#[no_mangle]
+pub extern "C" fn last_error_offset() -> i32 {
+    unsafe { LAST_ERROR_OFFSET }
+}
+
 fn get_ptr(offset: usize) -> *const u8 {
     unsafe { BUFFER.as_ptr().add(offset) }
 }
 
-fn str_from_buffer(from: usize, len: usize) -> &'static str {
-    unsafe { std::str::from_utf8(&BUFFER[from..(from + len)]).unwrap() }
+/// Decode the region `[from, from + len)` as UTF-8 through a `ReadView`
+fn str_from_buffer(from: usize, len: usize) -> Result<&'static str, BufferError> {
+    let view = ReadView::new(from, len).ok_or(BufferError::OutOfBounds)?;
+    view.as_str().map_err(BufferError::InvalidUtf8)
+}
+
+/// Write `src` into the region `[to, to + src.len())` through a `WriteView`
+fn copy_bytes(to: usize, src: &[u8]) -> Result<(), BufferError> {
+    let mut view = WriteView::new(to, src.len()).ok_or(BufferError::OutOfBounds)?;
+    view.copy_from_slice(src);
+    Ok(())
+}
+
+/// Encode a `BufferError` as the negative status code returned across the FFI boundary
+fn fail(e: BufferError) -> i32 {
+    match e {
+        BufferError::OutOfBounds => -1,
+        BufferError::InvalidUtf8(utf8_err) => {
+            let valid_up_to = utf8_err.valid_up_to();
+            unsafe {
+                LAST_ERROR_OFFSET = valid_up_to as i32;
+            }
+            -(valid_up_to as i32) - 1
+        }
+    }
+}
+
+/// Validate a caller-supplied length against the live region at `handle`, rejecting a negative length or one
+/// that would read past the end of that region (e.g. into a neighbouring region)
+fn region_len(handle: usize, len: i32) -> Option<usize> {
+    if len < 0 {
+        return None;
+    }
+
+    let len = len as usize;
+    let end = region_end(handle)?;
+    if handle + len > end {
+        return None;
+    }
+
+    Some(len)
+}
+
+/// End offset of the live region that `alloc` handed out at `handle`, or `None` if `handle` is not (or is no
+/// longer) a live region
+fn region_end(handle: usize) -> Option<usize> {
+    unsafe {
+        LIVE_REGIONS
+            .iter()
+            .find(|&&(start, _)| start == handle)
+            .map(|&(_, end)| end)
+    }
+}
+
+/// Scan the region `[from, to)` for a NUL byte, after the fashion of `CStr::from_ptr`, returning the length up
+/// to (but not including) the terminator, or `None` if the region contains no NUL
+fn cstr_len(from: usize, to: usize) -> Option<usize> {
+    unsafe { BUFFER[from..to].iter().position(|&byte| byte == NUL) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn write_region(handle: i32, bytes: &[u8]) {
+        BUFFER[(handle as usize)..(handle as usize + bytes.len())].copy_from_slice(bytes);
+    }
+
+    #[test]
+    fn set_name_rejects_a_length_that_overruns_its_own_region() {
+        unsafe {
+            reset();
+            let sal_handle = alloc(4);
+            write_region(sal_handle, b"Hi, ");
+            let name_handle = alloc(8);
+            write_region(name_handle, b"Chris   ");
+
+            assert_eq!(set_name(sal_handle, 8, name_handle, 5), -1);
+        }
+    }
+
+    #[test]
+    fn get_msg_len_does_not_see_a_later_plain_set_name() {
+        unsafe {
+            reset();
+            let sal_handle = alloc(16);
+            write_region(sal_handle, b"Good morning\0\0\0\0");
+            let name_handle = alloc(16);
+            write_region(name_handle, b"Christopher\0\0\0\0\0");
+            assert!(set_name_cstr(sal_handle, name_handle) > 0);
+            assert!(get_msg_len() > 0);
+
+            reset();
+            let sal_handle = alloc(2);
+            write_region(sal_handle, b"Hi");
+            let name_handle = alloc(2);
+            write_region(name_handle, b"Jo");
+
+            assert_eq!(set_name(sal_handle, 2, name_handle, 2), 7);
+            assert_eq!(get_msg_len(), -1);
+        }
+    }
+
+    #[test]
+    fn alloc_rejects_a_negative_length_instead_of_overflowing_the_cursor() {
+        unsafe {
+            reset();
+            assert!(alloc(10) >= 0);
+            assert_eq!(alloc(-5), -1);
+        }
+    }
+
+    #[test]
+    fn region_ptr_returns_null_for_a_handle_that_is_not_a_live_region() {
+        unsafe {
+            reset();
+            assert_eq!(region_ptr(999_999), std::ptr::null());
+        }
+    }
 }
@@ -4,6 +4,7 @@ const NAME_OFFSET: usize = 16;
 const MSG_OFFSET: usize = 32;
 
 static mut BUFFER: [u8; MEMORY_BUFFER_SIZE] = [0; MEMORY_BUFFER_SIZE];
+static mut LAST_ERROR_OFFSET: i32 = -1;
 
 #[no_mangle]
 pub unsafe extern "C" fn get_salutation_ptr() -> *const u8 {
@@ -21,12 +22,21 @@ pub unsafe extern "C" fn get_msg_ptr() -> *const u8 {
 }
 
 #[no_mangle]
-/// Place the formatted greeting at the known memory location and return the total length
+/// Place the formatted greeting at the known memory location and return the total length, or a negative status
+/// code if either region contains invalid UTF-8 (`-(valid_up_to as i32) - 1`; see `last_error_offset()`)
 ///
 /// The use an intermediate String object to hold the formatted greeting inadvertently causes Wasm memory growth
 pub unsafe extern "C" fn set_name(sal_len: i32, name_len: i32) -> i32 {
-    let sal: &str = str_from_buffer(SALUT_OFFSET, sal_len as usize);
-    let name: &str = str_from_buffer(NAME_OFFSET, name_len as usize);
+    LAST_ERROR_OFFSET = -1;
+
+    let sal: &str = match str_from_buffer(SALUT_OFFSET, sal_len as usize) {
+        Ok(s) => s,
+        Err(e) => return fail(e),
+    };
+    let name: &str = match str_from_buffer(NAME_OFFSET, name_len as usize) {
+        Ok(s) => s,
+        Err(e) => return fail(e),
+    };
 
     let greeting: String = format!("{}, {}!", sal, name);
 
@@ -41,10 +51,22 @@ pub unsafe extern "C" fn set_name(sal_len: i32, name_len: i32) -> i32 {
     greeting.len() as i32
 }
 
+/// Byte offset at which the most recent UTF-8 validation failure occurred, or `-1` if the last call succeeded
+#[no_mangle]
+pub unsafe extern "C" fn last_error_offset() -> i32 {
+    LAST_ERROR_OFFSET
+}
+
 unsafe fn get_ptr(offset: usize) -> *const u8 {
     BUFFER.as_ptr().add(offset)
 }
 
-unsafe fn str_from_buffer(from: usize, len: usize) -> &'static str {
-    std::str::from_utf8(&BUFFER[from..(from + len)]).unwrap()
+unsafe fn str_from_buffer(from: usize, len: usize) -> Result<&'static str, std::str::Utf8Error> {
+    std::str::from_utf8(&BUFFER[from..(from + len)])
+}
+
+unsafe fn fail(e: std::str::Utf8Error) -> i32 {
+    let valid_up_to = e.valid_up_to();
+    LAST_ERROR_OFFSET = valid_up_to as i32;
+    -(valid_up_to as i32) - 1
 }
@@ -9,6 +9,42 @@ const COMMA: u8 = 44;
 
 static mut BUFFER: [u8; MEMORY_BUFFER_SIZE] = [0; MEMORY_BUFFER_SIZE];
 
+/// Error returned when a push would write past a `StrBuf`'s capacity
+pub struct Overflow;
+
+/// A fixed-capacity, stack-style byte buffer with a `len` cursor
+///
+/// Every push verifies the write fits within `N` bytes before copying, so a `StrBuf` can never be made to write
+/// outside its own backing array
+struct StrBuf<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> StrBuf<N> {
+    fn new() -> Self {
+        StrBuf { buf: [0; N], len: 0 }
+    }
+
+    fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), Overflow> {
+        if self.len + bytes.len() > N {
+            return Err(Overflow);
+        }
+
+        self.buf[self.len..(self.len + bytes.len())].copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    fn push_ascii(&mut self, byte: u8) -> Result<(), Overflow> {
+        self.push_bytes(&[byte])
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
 /// Return the long-lived pointers to known memory locations
 #[no_mangle]
 pub unsafe extern "C" fn get_salutation_ptr() -> *const u8 {
@@ -26,31 +62,41 @@ pub unsafe extern "C" fn get_msg_ptr() -> *const u8 {
 }
 
 #[no_mangle]
-/// Place the formatted greeting at the known memory location and return the total length
+/// Place the formatted greeting at the known memory location and return the total length, or `-1` if the
+/// salutation and name don't fit within their own regions or within the message region
 ///
 /// Don't use an intermediate String object to hold the formatted greeting as this might cause Wasm memory growth
 pub unsafe extern "C" fn set_name(sal_len: i32, name_len: i32) -> i32 {
-    let mut idx: usize;
-
-    // Write salutation directly to the buffer
-    copy_bytes(MSG_OFFSET, SALUT_OFFSET, sal_len);
-    idx = MSG_OFFSET + sal_len as usize;
+    let sal = match checked_region(SALUT_OFFSET, NAME_OFFSET, sal_len) {
+        Some(bytes) => bytes,
+        None => return -1,
+    };
+    let name = match checked_region(NAME_OFFSET, MSG_OFFSET, name_len) {
+        Some(bytes) => bytes,
+        None => return -1,
+    };
 
-    // Write separator ", "
-    BUFFER[idx] = COMMA;
-    idx += 1;
-    BUFFER[idx] = SPACE;
-    idx += 1;
+    let mut msg = StrBuf::<{ MEMORY_BUFFER_SIZE - MSG_OFFSET }>::new();
 
-    // Write name
-    copy_bytes(idx, NAME_OFFSET, name_len);
-    idx += name_len as usize;
+    if msg.push_bytes(sal).is_err() {
+        return -1;
+    }
+    if msg.push_ascii(COMMA).is_err() {
+        return -1;
+    }
+    if msg.push_ascii(SPACE).is_err() {
+        return -1;
+    }
+    if msg.push_bytes(name).is_err() {
+        return -1;
+    }
+    if msg.push_ascii(BANG).is_err() {
+        return -1;
+    }
 
-    // Write bang character
-    BUFFER[idx] = BANG;
-    idx += 1;
+    BUFFER[MSG_OFFSET..(MSG_OFFSET + msg.len)].copy_from_slice(msg.as_bytes());
 
-    (idx - MSG_OFFSET) as i32
+    msg.len as i32
 }
 
 /// Helper functions
@@ -58,11 +104,36 @@ unsafe fn get_ptr(offset: usize) -> *const u8 {
     BUFFER.as_ptr().add(offset)
 }
 
-unsafe fn copy_bytes(to: usize, from: usize, len: i32) {
-    BUFFER[from..(from + len as usize)]
-        .iter()
-        .enumerate()
-        .for_each(|(idx, byte)| {
-            BUFFER[to + idx] = *byte;
-        })
+/// Slice of `[from, from + len)`, bounded against `region_end` and rejecting a negative `len`, so a
+/// caller-controlled length can never read past the region it claims to describe
+unsafe fn checked_region(from: usize, region_end: usize, len: i32) -> Option<&'static [u8]> {
+    if len < 0 {
+        return None;
+    }
+
+    let len = len as usize;
+    if from + len > region_end {
+        return None;
+    }
+
+    Some(&BUFFER[from..(from + len)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_name_rejects_oversized_salutation() {
+        unsafe {
+            assert_eq!(set_name(200, 3), -1);
+        }
+    }
+
+    #[test]
+    fn set_name_rejects_oversized_name() {
+        unsafe {
+            assert_eq!(set_name(3, 200), -1);
+        }
+    }
 }